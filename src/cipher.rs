@@ -0,0 +1,240 @@
+//! Poseidon-based authenticated encryption, mirroring the cipher module of
+//! Dusk's Poseidon252, built the same way [`crate::sponge`] is: a duplex
+//! construction on top of [`Permutation`].
+//!
+//! The first [`CAPACITY_WIDTH`] state elements are reserved and never
+//! carry plaintext/ciphertext, exactly as `sponge` reserves its single
+//! capacity element — except here they hold a domain tag, the shared
+//! secret and the nonce, so the keystream the remaining (rate) elements
+//! produce depends on all three. Plaintext is absorbed rate-many scalars
+//! at a time: each plaintext element is added to the matching rate word to
+//! produce a ciphertext element, which then replaces that rate word before
+//! the next permutation, so encryption and decryption only differ in
+//! which side of the addition is known. After the last block, one further
+//! permutation's capacity elements serve as the authentication tag.
+
+use crate::errors::PermError;
+use crate::permutation::Permutation;
+use bulletproofs::r1cs::{ConstraintSystem, LinearCombination, Variable};
+use curve25519_dalek::scalar::Scalar;
+use subtle::{Choice, ConstantTimeEq};
+
+/// Mixed into the first capacity element, so a cipher transcript cannot be
+/// confused with a [`crate::sponge`] one built on the same permutation.
+const DOMAIN_CIPHER: u64 = 2;
+/// Number of state elements, starting at index 0, reserved as capacity:
+/// the domain tag, the shared secret and the nonce. Everything from
+/// `CAPACITY_WIDTH` onward is rate and carries plaintext/ciphertext,
+/// exactly as in `sponge`.
+const CAPACITY_WIDTH: usize = 3;
+
+fn initial_state(t: usize, secret: Scalar, nonce: Scalar) -> Vec<Scalar> {
+    let mut state = vec![Scalar::zero(); t];
+    state[0] = Scalar::from(DOMAIN_CIPHER);
+    state[1] = secret;
+    state[2] = nonce;
+    state
+}
+
+fn permute(perm: &mut Permutation, state: Vec<Scalar>) -> Result<Vec<Scalar>, PermError> {
+    perm.reset();
+    perm.inputs(state)?;
+    perm.result()
+}
+
+/// Encrypt `plaintext` under `secret`/`nonce`, returning the ciphertext
+/// (same length as `plaintext`) and a `CAPACITY_WIDTH`-scalar
+/// authentication tag.
+pub fn encrypt(
+    secret: Scalar,
+    nonce: Scalar,
+    plaintext: &[Scalar],
+) -> Result<(Vec<Scalar>, Vec<Scalar>), PermError> {
+    let mut perm = Permutation::default();
+    let t = perm.width();
+    let rate = t - CAPACITY_WIDTH;
+
+    let mut state = permute(&mut perm, initial_state(t, secret, nonce))?;
+
+    let mut ciphertext = Vec::with_capacity(plaintext.len());
+    for chunk in plaintext.chunks(rate) {
+        for (i, m) in chunk.iter().enumerate() {
+            let c = state[CAPACITY_WIDTH + i] + m;
+            ciphertext.push(c);
+            state[CAPACITY_WIDTH + i] = c;
+        }
+        state = permute(&mut perm, state)?;
+    }
+
+    Ok((ciphertext, state[..CAPACITY_WIDTH].to_vec()))
+}
+
+/// Decrypt `ciphertext` under `secret`/`nonce`, checking it against `tag`
+/// in constant time. Returns [`PermError::TagMismatch`] if the recomputed
+/// tag disagrees, and [`PermError::InvalidParameterLength`] if `tag` is
+/// not `CAPACITY_WIDTH` scalars long.
+pub fn decrypt(
+    secret: Scalar,
+    nonce: Scalar,
+    ciphertext: &[Scalar],
+    tag: &[Scalar],
+) -> Result<Vec<Scalar>, PermError> {
+    if tag.len() != CAPACITY_WIDTH {
+        return Err(PermError::InvalidParameterLength);
+    }
+
+    let mut perm = Permutation::default();
+    let t = perm.width();
+    let rate = t - CAPACITY_WIDTH;
+
+    let mut state = permute(&mut perm, initial_state(t, secret, nonce))?;
+
+    let mut plaintext = Vec::with_capacity(ciphertext.len());
+    for chunk in ciphertext.chunks(rate) {
+        for (i, c) in chunk.iter().enumerate() {
+            plaintext.push(c - state[CAPACITY_WIDTH + i]);
+            state[CAPACITY_WIDTH + i] = *c;
+        }
+        state = permute(&mut perm, state)?;
+    }
+
+    let tag_matches = state[..CAPACITY_WIDTH]
+        .iter()
+        .zip(tag.iter())
+        .fold(Choice::from(1u8), |acc, (a, b)| acc & a.ct_eq(b));
+
+    if bool::from(tag_matches) {
+        Ok(plaintext)
+    } else {
+        Err(PermError::TagMismatch)
+    }
+}
+
+/// In-circuit counterpart of [`encrypt`].
+///
+/// As with [`crate::sponge::constrain_sponge`], every permutation call
+/// needs freshly allocated circuit variables for its output before the
+/// next one can consume them, so the caller drives the native cipher
+/// alongside the circuit one and supplies the already allocated
+/// [`Variable`]s for each permutation's input in `blocks` (the
+/// key-schedule block, then one block per absorbed ciphertext round). The
+/// returned linear combinations are the final permutation's capacity
+/// elements; the caller constrains them equal to the public tag.
+pub fn constrain_encrypt(
+    perm: &Permutation,
+    cs: &mut dyn ConstraintSystem,
+    blocks: Vec<Vec<Variable>>,
+) -> Result<Vec<LinearCombination>, PermError> {
+    let mut state: Vec<LinearCombination> = Vec::new();
+    for block in blocks {
+        state = perm.constrain_result(cs, block)?;
+    }
+
+    Ok(state[..CAPACITY_WIDTH].to_vec())
+}
+
+/// In-circuit counterpart of [`decrypt`]. The duplex absorption that
+/// `constrain_encrypt` proves is identical regardless of direction, so
+/// this only differs from it in name: the caller forms `blocks` from the
+/// ciphertext side and constrains the returned tag equal to the value
+/// being checked.
+pub fn constrain_decrypt(
+    perm: &Permutation,
+    cs: &mut dyn ConstraintSystem,
+    blocks: Vec<Vec<Variable>>,
+) -> Result<Vec<LinearCombination>, PermError> {
+    constrain_encrypt(perm, cs, blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_encrypts_and_decrypts() {
+        let rate = Permutation::default().width() - CAPACITY_WIDTH;
+        let secret = Scalar::from(7u64);
+        let nonce = Scalar::from(42u64);
+        let plaintext: Vec<Scalar> = (0..rate + 2).map(|i| Scalar::from(i as u64 + 1)).collect();
+
+        let (ciphertext, tag) = encrypt(secret, nonce, &plaintext).unwrap();
+        let decrypted = decrypt(secret, nonce, &ciphertext, &tag).unwrap();
+
+        assert_eq!(decrypted, plaintext);
+    }
+
+    /// A tag computed for one ciphertext must not verify against another:
+    /// flipping a single ciphertext scalar must change the recomputed tag
+    /// and so be rejected, rather than silently decrypting.
+    #[test]
+    fn tampered_ciphertext_is_rejected() {
+        let secret = Scalar::from(7u64);
+        let nonce = Scalar::from(42u64);
+        let plaintext = vec![Scalar::from(1u64), Scalar::from(2u64), Scalar::from(3u64)];
+
+        let (mut ciphertext, tag) = encrypt(secret, nonce, &plaintext).unwrap();
+        ciphertext[0] += Scalar::one();
+
+        assert_eq!(
+            decrypt(secret, nonce, &ciphertext, &tag),
+            Err(PermError::TagMismatch)
+        );
+    }
+
+    #[test]
+    fn decrypt_rejects_wrong_tag_length() {
+        let plaintext = vec![Scalar::from(1u64)];
+        let (ciphertext, mut tag) = encrypt(Scalar::from(7u64), Scalar::from(42u64), &plaintext).unwrap();
+        tag.pop();
+
+        assert_eq!(
+            decrypt(Scalar::from(7u64), Scalar::from(42u64), &ciphertext, &tag),
+            Err(PermError::InvalidParameterLength)
+        );
+    }
+
+    #[test]
+    fn constrain_encrypt_matches_native() {
+        use bulletproofs::r1cs::Prover;
+        use bulletproofs::PedersenGens;
+        use merlin::Transcript;
+
+        let perm = Permutation::default();
+        let rate = perm.width() - CAPACITY_WIDTH;
+        let secret = Scalar::from(7u64);
+        let nonce = Scalar::from(42u64);
+        let plaintext: Vec<Scalar> = (0..rate).map(|i| Scalar::from(i as u64 + 1)).collect();
+
+        let (ciphertext, expected_tag) = encrypt(secret, nonce, &plaintext).unwrap();
+
+        let pc_gens = PedersenGens::default();
+        let mut transcript = Transcript::new(b"cipher-circuit-test");
+        let mut prover = Prover::new(&pc_gens, &mut transcript);
+
+        // Mirror `encrypt`'s own block layout: the key-schedule block, then
+        // one absorbed block per ciphertext chunk. The absorb block's
+        // capacity words are whatever the key-schedule permutation actually
+        // output, not a fresh zero state: `encrypt` keeps permuting the
+        // same running `state`, it never restarts it.
+        let key_block = initial_state(perm.width(), secret, nonce);
+        let mut key_perm = Permutation::default();
+        key_perm.reset();
+        key_perm.inputs(key_block.clone()).unwrap();
+        let mut absorb_block = key_perm.result().unwrap();
+        absorb_block[CAPACITY_WIDTH..].copy_from_slice(&ciphertext);
+
+        let key_vars: Vec<Variable> = key_block
+            .iter()
+            .map(|s| prover.allocate(Some(*s)).unwrap())
+            .collect();
+        let absorb_vars: Vec<Variable> = absorb_block
+            .iter()
+            .map(|s| prover.allocate(Some(*s)).unwrap())
+            .collect();
+
+        let tag = constrain_encrypt(&perm, &mut prover, vec![key_vars, absorb_vars]).unwrap();
+
+        let actual_tag: Vec<Scalar> = tag.iter().map(|lc| prover.eval(lc)).collect();
+        assert_eq!(actual_tag, expected_tag);
+    }
+}
@@ -0,0 +1,71 @@
+//! `Elt`: either an already-allocated circuit [`Variable`] or a
+//! not-yet-allocated [`LinearCombination`], each optionally carrying the
+//! native [`Scalar`] it represents.
+//!
+//! Additions and constant folds over `Elt`s stay `Lc` — no r1cs constraint
+//! is emitted for them, since linear combinations are free. Only a
+//! multiplication forces allocation, by handing both operands' linear
+//! combinations to `cs.multiply` and keeping its output variable. This
+//! mirrors the `circuit2` accumulation technique from neptune's Poseidon
+//! circuit, and is what lets round-key additions and MDS diffusion in
+//! [`crate::specification::CircuitSpec`] cost zero constraints instead of
+//! one per word per round.
+//!
+//! The carried witness has nothing to do with proving correctness of the
+//! r1cs constraints themselves — `cs.multiply` derives those on its own.
+//! It exists only so that operations with no linear-combination
+//! representation, like the `Alpha::Inverse` S-box's field inversion, can
+//! get at the value they need to invert without reading it back out of the
+//! constraint system (which `Prover` exposes for tests but `Verifier`,
+//! having no witnesses at all, cannot). Whoever allocates the first `Elt`
+//! in a computation — e.g. a prover with its own input scalars on hand —
+//! is responsible for attaching the witness; every `Elt`-to-`Elt` operation
+//! below just carries it forward.
+
+use bulletproofs::r1cs::{LinearCombination, Variable};
+use curve25519_dalek::scalar::Scalar;
+
+#[derive(Clone)]
+pub enum Elt {
+    Allocated(Variable, Option<Scalar>),
+    Lc(LinearCombination, Option<Scalar>),
+}
+
+impl Elt {
+    /// An `Elt` wrapping an already-allocated `Variable`, together with the
+    /// native value it was allocated with.
+    pub fn allocated(var: Variable, witness: Scalar) -> Self {
+        Elt::Allocated(var, Some(witness))
+    }
+
+    /// View this `Elt` as a `LinearCombination`, without allocating
+    /// anything: an `Allocated` variable is simply wrapped, a pending `Lc`
+    /// is returned as-is.
+    pub fn lc(&self) -> LinearCombination {
+        match self {
+            Elt::Allocated(var, _) => LinearCombination::from(*var),
+            Elt::Lc(lc, _) => lc.clone(),
+        }
+    }
+
+    /// The native value this `Elt` represents, if whoever constructed it
+    /// (transitively) supplied one.
+    pub fn witness(&self) -> Option<Scalar> {
+        match self {
+            Elt::Allocated(_, w) => *w,
+            Elt::Lc(_, w) => *w,
+        }
+    }
+}
+
+impl From<Variable> for Elt {
+    fn from(var: Variable) -> Self {
+        Elt::Allocated(var, None)
+    }
+}
+
+impl From<LinearCombination> for Elt {
+    fn from(lc: LinearCombination) -> Self {
+        Elt::Lc(lc, None)
+    }
+}
@@ -0,0 +1,48 @@
+use std::fmt;
+
+/// Errors that can occur while configuring or running the Hades252
+/// permutation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermError {
+    /// `full_rounds` must be even, since it is split into two equal halves
+    /// applied before and after the partial rounds.
+    FullRoundsOdd,
+    /// Adding the given scalars would take the permutation's input buffer
+    /// past `t` scalars.
+    InputFull,
+    /// The round-constants iterator was exhausted before every round of
+    /// the permutation consumed its constant. Also returned by
+    /// [`crate::sponge::constrain_sponge`] when `squeeze_blocks` holds
+    /// fewer blocks than squeezing `output_len` scalars needs.
+    NoMoreConstants,
+    /// The requested S-box exponent `alpha` is not coprime to `p - 1`, so
+    /// `x -> x^alpha` would not be a permutation of the field.
+    InvalidAlpha,
+    /// A supplied round-constants vector or MDS matrix does not have the
+    /// dimensions the permutation's `t`/`full_rounds`/`partial_rounds`
+    /// expect.
+    InvalidParameterLength,
+    /// A supplied MDS matrix failed the MDS (no singular minor) check.
+    MatrixNotMDS,
+    /// [`crate::cipher::decrypt`] recomputed an authentication tag that did
+    /// not match the one supplied alongside the ciphertext.
+    TagMismatch,
+}
+
+impl fmt::Display for PermError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PermError::FullRoundsOdd => write!(f, "full_rounds must be an even number"),
+            PermError::InputFull => write!(f, "permutation input buffer is full"),
+            PermError::NoMoreConstants => write!(f, "ran out of round constants"),
+            PermError::InvalidAlpha => write!(f, "S-box exponent is not coprime to p - 1"),
+            PermError::InvalidParameterLength => {
+                write!(f, "supplied parameter has the wrong length")
+            }
+            PermError::MatrixNotMDS => write!(f, "supplied matrix is not MDS"),
+            PermError::TagMismatch => write!(f, "authentication tag does not match"),
+        }
+    }
+}
+
+impl std::error::Error for PermError {}
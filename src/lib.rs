@@ -0,0 +1,13 @@
+//! Hades252 — an instantiation of the Hades strategy for a partial-SPN
+//! permutation over `curve25519_dalek::scalar::Scalar`, with both a native
+//! evaluation path and a bulletproofs r1cs circuit path for every
+//! operation.
+
+pub mod cipher;
+pub mod elt;
+pub mod errors;
+pub mod mds_matrix;
+pub mod permutation;
+pub mod round_constants;
+pub mod specification;
+pub mod sponge;
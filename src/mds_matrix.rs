@@ -0,0 +1,355 @@
+use crate::errors::PermError;
+use bulletproofs::r1cs::LinearCombination;
+use curve25519_dalek::scalar::Scalar;
+use sha2::{Digest, Sha512};
+
+/// A square Maximum Distance Separable (MDS) matrix used to diffuse the
+/// permutation state between S-box layers.
+#[derive(Debug, Clone)]
+pub struct MDSMatrix {
+    t: usize,
+    rows: Vec<Vec<Scalar>>,
+}
+
+impl MDSMatrix {
+    /// Generate the canonical Cauchy MDS matrix for a state of width `t`,
+    /// with no seed.
+    pub fn generate(t: usize) -> Self {
+        let rows = (0..t)
+            .map(|i| {
+                (0..t)
+                    .map(|j| {
+                        let x = Scalar::from((i + t) as u64);
+                        let y = Scalar::from(j as u64);
+                        (x - y).invert()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        MDSMatrix { t, rows }
+    }
+
+    /// Generate a Cauchy MDS matrix whose `x`/`y` parameters are derived
+    /// from `hash(tag || seed || index)` instead of the small integers
+    /// `generate` uses, so that two deployments with different `tag`/
+    /// `seed` values get distinct-but-reproducible matrices.
+    pub fn generate_with_seed(t: usize, tag: &[u8], seed: &[u8]) -> Self {
+        let scalar_at = |i: usize| -> Scalar {
+            let mut hasher = Sha512::new();
+            hasher.update(tag);
+            hasher.update(seed);
+            hasher.update(&(i as u64).to_le_bytes());
+            Scalar::hash_from_bytes::<Sha512>(&hasher.finalize())
+        };
+
+        let xs: Vec<Scalar> = (0..t).map(scalar_at).collect();
+        let ys: Vec<Scalar> = (t..2 * t).map(scalar_at).collect();
+
+        let rows = xs
+            .iter()
+            .map(|x| ys.iter().map(|y| (x - y).invert()).collect())
+            .collect();
+
+        MDSMatrix { t, rows }
+    }
+
+    /// Use an externally supplied MDS matrix verbatim, e.g. an audited
+    /// parameter set, after checking it is `t x t` and passes a best-effort
+    /// MDS check (see [`is_mds`]).
+    pub fn from_matrix(rows: Vec<Vec<Scalar>>, t: usize) -> Result<Self, PermError> {
+        if rows.len() != t || rows.iter().any(|row| row.len() != t) {
+            return Err(PermError::InvalidParameterLength);
+        }
+        if !is_mds(&rows) {
+            return Err(PermError::MatrixNotMDS);
+        }
+
+        Ok(MDSMatrix { t, rows })
+    }
+
+    pub fn width(&self) -> usize {
+        self.t
+    }
+
+    pub fn rows(&self) -> &[Vec<Scalar>] {
+        &self.rows
+    }
+
+    pub fn mul_vector(&self, v: &[Scalar]) -> Vec<Scalar> {
+        self.rows
+            .iter()
+            .map(|row| row.iter().zip(v.iter()).map(|(a, b)| a * b).sum())
+            .collect()
+    }
+
+    pub fn constrain_mul_vector(&self, v: Vec<LinearCombination>) -> Vec<LinearCombination> {
+        self.rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(v.iter())
+                    .map(|(a, b)| b.clone() * *a)
+                    .fold(LinearCombination::default(), |acc, term| acc + term)
+            })
+            .collect()
+    }
+
+    /// Factor the `partial_rounds`-many applications of this matrix into a
+    /// single dense "pre-sparse" matrix applied once for the first partial
+    /// round, followed by `partial_rounds - 1` [`SparseMDSMatrix`]s — one
+    /// per remaining round — each costing `2t-1` multiplications instead of
+    /// `t^2`.
+    ///
+    /// This is the standard Poseidon partial-round optimization: since the
+    /// S-box in a partial round only ever touches `new_words[0]`, the dense
+    /// MDS layer used by the *last* round can be rewritten as
+    /// `M_hat * M_prime`, where `M_prime` is the identity in its first
+    /// row/column and agrees with `matrix` on everything else, and `M_hat`
+    /// is sparse (dense first row/column, identity elsewhere). Because
+    /// `M_prime` never touches word 0, it commutes past that round's S-box
+    /// and can be folded leftward into the matrix used by the *previous*
+    /// round instead (`M_prime * matrix`). Repeating this fold from the
+    /// last partial round back to the second produces, in reverse order,
+    /// the sparse matrix each round 1..partial_rounds needs, plus one
+    /// residual dense matrix — having absorbed every fold — applied by
+    /// round 0.
+    pub fn factorize_alpha(&self, partial_rounds: usize) -> (MDSMatrix, Vec<SparseMDSMatrix>) {
+        let mut matrix = self.rows.clone();
+        let rounds_to_factor = partial_rounds.saturating_sub(1);
+        let mut sparse_matrices = Vec::with_capacity(rounds_to_factor);
+
+        for _ in 0..rounds_to_factor {
+            let m_prime = lower_right_identity_block(&matrix);
+            let m_prime_inv = invert(&m_prime);
+            let m_hat = mat_mul(&matrix, &m_prime_inv);
+
+            sparse_matrices.push(SparseMDSMatrix {
+                row: m_hat[0].clone(),
+                col_hat: (1..self.t).map(|i| m_hat[i][0]).collect(),
+            });
+
+            matrix = mat_mul(&m_prime, &self.rows);
+        }
+
+        sparse_matrices.reverse();
+        (
+            MDSMatrix {
+                t: self.t,
+                rows: matrix,
+            },
+            sparse_matrices,
+        )
+    }
+}
+
+/// A matrix that is the identity except for a dense first row and first
+/// column, produced by [`MDSMatrix::factorize_alpha`] and applied in the
+/// optimized partial rounds of [`crate::permutation::Permutation`].
+#[derive(Debug, Clone)]
+pub struct SparseMDSMatrix {
+    /// First row of the matrix, length `t`.
+    row: Vec<Scalar>,
+    /// First column, excluding the `(0, 0)` entry already held in `row[0]`,
+    /// length `t - 1`.
+    col_hat: Vec<Scalar>,
+}
+
+impl SparseMDSMatrix {
+    pub fn mul_vector(&self, v: &[Scalar]) -> Vec<Scalar> {
+        let mut out = vec![Scalar::zero(); v.len()];
+        out[0] = self.row.iter().zip(v.iter()).map(|(a, b)| a * b).sum();
+        for i in 1..v.len() {
+            out[i] = self.col_hat[i - 1] * v[0] + v[i];
+        }
+        out
+    }
+
+    pub fn constrain_mul_vector(&self, v: Vec<LinearCombination>) -> Vec<LinearCombination> {
+        let mut out = Vec::with_capacity(v.len());
+        out.push(
+            self.row
+                .iter()
+                .zip(v.iter())
+                .map(|(a, b)| b.clone() * *a)
+                .fold(LinearCombination::default(), |acc, term| acc + term),
+        );
+        for i in 1..v.len() {
+            out.push(v[i].clone() + v[0].clone() * self.col_hat[i - 1]);
+        }
+        out
+    }
+}
+
+fn identity(t: usize) -> Vec<Vec<Scalar>> {
+    (0..t)
+        .map(|i| {
+            (0..t)
+                .map(|j| if i == j { Scalar::one() } else { Scalar::zero() })
+                .collect()
+        })
+        .collect()
+}
+
+/// Builds `M_prime`: the identity in row/column 0, and `matrix`'s own
+/// lower-right `(t-1) x (t-1)` block everywhere else.
+fn lower_right_identity_block(matrix: &[Vec<Scalar>]) -> Vec<Vec<Scalar>> {
+    let t = matrix.len();
+    let mut m_prime = identity(t);
+    for (i, row) in m_prime.iter_mut().enumerate().skip(1) {
+        for (j, entry) in row.iter_mut().enumerate().skip(1) {
+            *entry = matrix[i][j];
+        }
+    }
+    m_prime
+}
+
+fn mat_mul(a: &[Vec<Scalar>], b: &[Vec<Scalar>]) -> Vec<Vec<Scalar>> {
+    let t = a.len();
+    (0..t)
+        .map(|i| {
+            (0..t)
+                .map(|j| (0..t).map(|k| a[i][k] * b[k][j]).sum())
+                .collect()
+        })
+        .collect()
+}
+
+pub(crate) fn mat_vec_mul(a: &[Vec<Scalar>], v: &[Scalar]) -> Vec<Scalar> {
+    a.iter()
+        .map(|row| row.iter().zip(v.iter()).map(|(a, b)| a * b).sum())
+        .collect()
+}
+
+/// Gauss-Jordan inversion with partial pivoting. `M_prime` is always
+/// invertible by construction (it is the identity extended by the
+/// lower-right block of an MDS matrix, which is itself MDS and therefore
+/// non-singular) — but that only guarantees *some* row/column ordering
+/// has every leading principal minor non-zero, not this one, so a
+/// non-zero pivot can still need to be swapped up from a later row.
+pub(crate) fn invert(matrix: &[Vec<Scalar>]) -> Vec<Vec<Scalar>> {
+    let t = matrix.len();
+    let mut a = matrix.to_vec();
+    let mut inv = identity(t);
+
+    for col in 0..t {
+        let pivot_row = (col..t)
+            .find(|&row| a[row][col] != Scalar::zero())
+            .expect("matrix passed to invert() is singular");
+        a.swap(col, pivot_row);
+        inv.swap(col, pivot_row);
+
+        let pivot = a[col][col].invert();
+        for j in 0..t {
+            a[col][j] *= pivot;
+            inv[col][j] *= pivot;
+        }
+
+        let a_col = a[col].clone();
+        let inv_col = inv[col].clone();
+        for row in 0..t {
+            if row == col {
+                continue;
+            }
+            let factor = a[row][col];
+            for j in 0..t {
+                a[row][j] -= factor * a_col[j];
+                inv[row][j] -= factor * inv_col[j];
+            }
+        }
+    }
+
+    inv
+}
+
+/// Gauss-Jordan elimination with a singularity check, instead of assuming
+/// (as `invert` does) that the matrix is already known to be invertible.
+fn is_invertible(matrix: &[Vec<Scalar>]) -> bool {
+    let t = matrix.len();
+    let mut a = matrix.to_vec();
+
+    for col in 0..t {
+        let pivot_row = match (col..t).find(|&row| a[row][col] != Scalar::zero()) {
+            Some(row) => row,
+            None => return false,
+        };
+        a.swap(col, pivot_row);
+
+        let pivot_inv = a[col][col].invert();
+        for j in 0..t {
+            a[col][j] *= pivot_inv;
+        }
+
+        let a_col = a[col].clone();
+        for row in 0..t {
+            if row == col {
+                continue;
+            }
+            let factor = a[row][col];
+            for j in 0..t {
+                a[row][j] -= factor * a_col[j];
+            }
+        }
+    }
+
+    true
+}
+
+/// Best-effort MDS check used by [`MDSMatrix::from_matrix`]: a matrix is
+/// MDS iff every square submatrix is non-singular. Checking submatrices of
+/// every size is exponential in `t`, so this checks the matrix itself and
+/// every `(t-1) x (t-1)` minor obtained by deleting one row and one
+/// column — the case most likely to fail for a matrix that was not
+/// already built from a Cauchy/Vandermonde construction.
+fn is_mds(matrix: &[Vec<Scalar>]) -> bool {
+    let t = matrix.len();
+    if !is_invertible(matrix) {
+        return false;
+    }
+
+    for skip_row in 0..t {
+        for skip_col in 0..t {
+            let minor: Vec<Vec<Scalar>> = matrix
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != skip_row)
+                .map(|(_, row)| {
+                    row.iter()
+                        .enumerate()
+                        .filter(|(j, _)| *j != skip_col)
+                        .map(|(_, v)| *v)
+                        .collect()
+                })
+                .collect();
+            if !is_invertible(&minor) {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `invert` must not assume `matrix[col][col]` is already a usable
+    /// pivot: this matrix is invertible but has a zero at `(0, 0)`, so
+    /// naive elimination without a row swap would corrupt row 0 (`Scalar`
+    /// inversion of zero doesn't panic, it just returns a wrong value) and
+    /// then every later row through back-substitution.
+    #[test]
+    fn invert_handles_a_zero_leading_pivot() {
+        let matrix = vec![
+            vec![Scalar::zero(), Scalar::one(), Scalar::zero()],
+            vec![Scalar::one(), Scalar::one(), Scalar::one()],
+            vec![Scalar::zero(), Scalar::zero(), Scalar::from(2u64)],
+        ];
+
+        let inv = invert(&matrix);
+        let product = mat_mul(&matrix, &inv);
+
+        assert_eq!(product, identity(3));
+    }
+}
@@ -1,10 +1,18 @@
+use crate::elt::Elt;
 use crate::errors::PermError;
 use crate::mds_matrix::*;
 use crate::round_constants::*;
+use crate::specification::{Alpha, CircuitSpec, NativeSpec, Specification};
 use bulletproofs::r1cs::{ConstraintSystem, LinearCombination, Variable};
 use curve25519_dalek::scalar::Scalar;
 use sha2::Sha512;
 
+/// Domain-separation tags passed to [`RoundConstants::generate_with_seed`]
+/// / [`MDSMatrix::generate_with_seed`] so that the two parameter sets never
+/// collide, even when derived from the same seed.
+const CONSTANTS_DOMAIN_TAG: &[u8] = b"hades252-permutation-constants";
+const MATRIX_DOMAIN_TAG: &[u8] = b"hades252-permutation-matrix";
+
 pub struct Permutation {
     t: usize,
     full_rounds: usize,
@@ -14,6 +22,14 @@ pub struct Permutation {
 
     constants: RoundConstants,
     matrix: MDSMatrix,
+    alpha: Alpha,
+
+    // Precomputed factorization of `matrix` used to run the partial rounds
+    // at ~2t-1 multiplications each instead of t^2; see
+    // `apply_partial_rounds_optimized`.
+    pre_sparse_matrix: MDSMatrix,
+    sparse_matrices: Vec<SparseMDSMatrix>,
+    optimized_partial_constants: Vec<Scalar>,
 }
 
 impl Default for Permutation {
@@ -21,36 +37,121 @@ impl Default for Permutation {
         let width = 9;
         let full_founds = 8;
         let partial_rounds = 59;
+        let constants = RoundConstants::generate(full_founds, partial_rounds, width);
+        let matrix = MDSMatrix::generate(width);
+        let (optimized_partial_constants, pre_sparse_matrix, sparse_matrices) =
+            Permutation::optimize_partial_rounds(width, full_founds, partial_rounds, &constants, &matrix);
+
         Permutation {
             t: width,
             full_rounds: full_founds,
             partial_rounds: partial_rounds,
             data: Vec::with_capacity(width),
-            constants: RoundConstants::generate(full_founds, partial_rounds, width),
-            matrix: MDSMatrix::generate(width),
+            constants,
+            matrix,
+            alpha: Alpha::Exponent(5),
+            pre_sparse_matrix,
+            sparse_matrices,
+            optimized_partial_constants,
         }
     }
 }
 
 impl Permutation {
-    pub fn new(t: usize, full_rounds: usize, partial_rounds: usize) -> Result<Self, PermError> {
+    pub fn new(
+        t: usize,
+        full_rounds: usize,
+        partial_rounds: usize,
+        alpha: Alpha,
+    ) -> Result<Self, PermError> {
+        Permutation::with_params(t, full_rounds, partial_rounds, alpha, None, None, None)
+    }
+
+    /// Like [`Permutation::new`], but lets the caller steer parameter
+    /// generation instead of always using the unseeded defaults:
+    ///
+    /// - `seed`, when given, is mixed into both the round-constants and
+    ///   the MDS-matrix derivation (each under its own domain tag), so two
+    ///   deployments that pass different seeds get distinct-but-reproducible
+    ///   instances, and the same seed always reproduces the same instance.
+    /// - `round_constants` / `mds_matrix`, when given, are used verbatim
+    ///   instead of being derived, after being validated — e.g. to load an
+    ///   audited parameter set or to match a reference test vector from
+    ///   another implementation.
+    pub fn with_params(
+        t: usize,
+        full_rounds: usize,
+        partial_rounds: usize,
+        alpha: Alpha,
+        seed: Option<&[u8]>,
+        round_constants: Option<Vec<Scalar>>,
+        mds_matrix: Option<MDSMatrix>,
+    ) -> Result<Self, PermError> {
         // We could ask for R_f instead of R_F then multiply by two.
         // It would make for a better API, however would need to be documented correctly
         // Because partial rounds means "everything", while full_rounds would mean "half of the full rounds"
         if full_rounds % 2 != 0 {
             return Err(PermError::FullRoundsOdd);
         }
+        if !alpha.is_valid() {
+            return Err(PermError::InvalidAlpha);
+        }
+
+        let constants = match round_constants {
+            Some(c) => RoundConstants::from_constants(c, full_rounds, partial_rounds, t)?,
+            None => match seed {
+                Some(seed) => RoundConstants::generate_with_seed(
+                    full_rounds,
+                    partial_rounds,
+                    t,
+                    CONSTANTS_DOMAIN_TAG,
+                    seed,
+                ),
+                None => RoundConstants::generate(full_rounds, partial_rounds, t),
+            },
+        };
+        let matrix = match mds_matrix {
+            Some(matrix) if matrix.width() != t => return Err(PermError::InvalidParameterLength),
+            Some(matrix) => matrix,
+            None => match seed {
+                Some(seed) => MDSMatrix::generate_with_seed(t, MATRIX_DOMAIN_TAG, seed),
+                None => MDSMatrix::generate(t),
+            },
+        };
+        let (optimized_partial_constants, pre_sparse_matrix, sparse_matrices) =
+            Permutation::optimize_partial_rounds(t, full_rounds, partial_rounds, &constants, &matrix);
 
-        let perm = Permutation {
+        Ok(Permutation {
             t: t,
             full_rounds: full_rounds,
             partial_rounds: partial_rounds,
             data: Vec::with_capacity(t),
-            constants: RoundConstants::generate(full_rounds, partial_rounds, t),
-            matrix: MDSMatrix::generate(t),
-        };
+            constants,
+            matrix,
+            alpha,
+            pre_sparse_matrix,
+            sparse_matrices,
+            optimized_partial_constants,
+        })
+    }
 
-        Ok(perm)
+    /// Factor `matrix` into the sparse-matrix sequence used by
+    /// `apply_partial_rounds_optimized`, and fold the partial-round
+    /// constants added to words `1..t` into a single scalar per round.
+    fn optimize_partial_rounds(
+        t: usize,
+        full_rounds: usize,
+        partial_rounds: usize,
+        constants: &RoundConstants,
+        matrix: &MDSMatrix,
+    ) -> (Vec<Scalar>, MDSMatrix, Vec<SparseMDSMatrix>) {
+        let half_full = (full_rounds / 2) * t;
+        let partial_region = &constants.as_slice()[half_full..half_full + partial_rounds * t];
+        let optimized_partial_constants =
+            fold_partial_round_constants(partial_region, matrix.rows(), partial_rounds, t);
+        let (pre_sparse_matrix, sparse_matrices) = matrix.factorize_alpha(partial_rounds);
+
+        (optimized_partial_constants, pre_sparse_matrix, sparse_matrices)
     }
     pub fn inputs(&mut self, scalars: Vec<Scalar>) -> Result<(), PermError> {
         let amount_to_add = scalars.len();
@@ -77,6 +178,9 @@ impl Permutation {
     pub fn width_left(&self) -> usize {
         self.t - self.data.len()
     }
+    pub fn width(&self) -> usize {
+        self.t
+    }
     pub fn input_bytes(&mut self, bytes: &[u8]) -> Result<(), PermError> {
         // Map arbitrary bytes to group using elligator2
         let scalar = Scalar::hash_from_bytes::<Sha512>(bytes);
@@ -93,170 +197,353 @@ impl Permutation {
 
 impl Permutation {
     pub fn result(&self) -> Result<Vec<Scalar>, PermError> {
+        self.permute(&mut NativeSpec { alpha: self.alpha }, self.data.clone())
+    }
+
+    pub fn constrain_result(
+        &self,
+        cs: &mut dyn ConstraintSystem,
+        words: Vec<Variable>,
+    ) -> Result<Vec<LinearCombination>, PermError> {
+        let words = words.into_iter().map(Elt::from).collect();
+        let result = self.permute(
+            &mut CircuitSpec {
+                cs,
+                alpha: self.alpha,
+            },
+            words,
+        )?;
+        Ok(result.iter().map(Elt::lc).collect())
+    }
+
+    /// Reference, unoptimized evaluation of the permutation: every partial
+    /// round uses the dense `matrix` and adds its own full constant vector.
+    /// Kept to validate `result` against; see the `optimized_partial_rounds_match_reference` test.
+    #[cfg(test)]
+    fn result_reference(&self) -> Result<Vec<Scalar>, PermError> {
+        let mut spec = NativeSpec { alpha: self.alpha };
         let mut constants_iter = self.constants.iter();
 
         let mut new_words: Vec<Scalar> = self.data.clone();
 
-        // Apply R_f full rounds
         for _ in 0..self.full_rounds / 2 {
-            new_words = self.apply_full_round(&mut constants_iter, new_words)?;
+            new_words = self.round(&mut spec, &mut constants_iter, new_words, true)?;
         }
 
-        // Apply R_P partial rounds
         for _ in 0..self.partial_rounds {
-            new_words = self.apply_partial_round(&mut constants_iter, new_words)?;
+            new_words = self.round(&mut spec, &mut constants_iter, new_words, false)?;
         }
 
-        // Apply R_f full rounds
         for _ in 0..self.full_rounds / 2 {
-            new_words = self.apply_full_round(&mut constants_iter, new_words)?;
+            new_words = self.round(&mut spec, &mut constants_iter, new_words, true)?;
         }
 
         Ok(new_words)
     }
+}
 
-    pub fn constrain_result(
+// The permutation, expressed once in terms of `Specification` and shared by
+// both the native and the in-circuit evaluation.
+impl Permutation {
+    fn permute<S: Specification>(
         &self,
-        cs: &mut dyn ConstraintSystem,
-        words: Vec<Variable>,
-    ) -> Result<Vec<LinearCombination>, PermError> {
+        spec: &mut S,
+        words: Vec<S::Field>,
+    ) -> Result<Vec<S::Field>, PermError> {
         let mut constants_iter = self.constants.iter();
 
-        let mut new_words: Vec<LinearCombination> = words.iter().map(|&var| var.into()).collect();
+        let mut new_words = words;
 
         // Apply R_f full rounds
         for _ in 0..self.full_rounds / 2 {
-            new_words = self.constrain_apply_full_round(&mut constants_iter, new_words, cs)?;
+            new_words = self.round(spec, &mut constants_iter, new_words, true)?;
         }
 
-        // Apply R_P partial rounds
-        for _ in 0..self.partial_rounds {
-            new_words = self.constrain_apply_partial_round(&mut constants_iter, new_words, cs)?;
+        // Apply R_P partial rounds, using the sparse-matrix factorization
+        // instead of the dense `matrix` on every round.
+        new_words = self.apply_partial_rounds_optimized(spec, new_words)?;
+        // The partial-round constants above come from
+        // `optimized_partial_constants`, not `constants_iter`; skip past
+        // that region so the trailing full rounds keep reading theirs.
+        for _ in 0..self.partial_rounds * self.t {
+            constants_iter.next();
         }
 
         // Apply R_f full rounds
         for _ in 0..self.full_rounds / 2 {
-            new_words = self.constrain_apply_full_round(&mut constants_iter, new_words, cs)?;
+            new_words = self.round(spec, &mut constants_iter, new_words, true)?;
         }
 
         Ok(new_words)
     }
-}
 
-// Apply partial rounds
-impl Permutation {
-    fn apply_partial_round(
-        &self,
-        constants: &mut RoundConstantsIterator,
-        words: Vec<Scalar>,
-    ) -> Result<Vec<Scalar>, PermError> {
-        // Add round keys to each word
-        let mut new_words = self.add_round_key(constants, words)?;
-        // Then apply quintic s-box to first element
-        new_words[0] = Permutation::quintic_s_box(&new_words[0]);
-        // Multiply this result by the MDS matrix
-        Ok(self.matrix.mul_vector(&new_words))
-    }
-    fn constrain_apply_partial_round(
+    /// One full or partial round: add round keys, apply the S-box (to
+    /// every word if `full`, otherwise only to the first), then diffuse
+    /// through the dense MDS matrix.
+    fn round<S: Specification>(
         &self,
+        spec: &mut S,
         constants: &mut RoundConstantsIterator,
-        words: Vec<LinearCombination>,
-        cs: &mut dyn ConstraintSystem,
-    ) -> Result<Vec<LinearCombination>, PermError> {
-        // Add round keys to each word
-        let mut new_words = self.constrain_add_round_key(constants, words)?;
-        // Then apply quintic s-box to first element
-        new_words[0] = Permutation::constrain_quintic_s_box(new_words[0].clone(), cs);
-        // Multiply this result by the MDS matrix
-        Ok(self.matrix.constrain_mul_vector(new_words))
+        words: Vec<S::Field>,
+        full: bool,
+    ) -> Result<Vec<S::Field>, PermError> {
+        let mut new_words: Vec<S::Field> = words
+            .iter()
+            .map(|word| {
+                let c = constants.next().ok_or(PermError::NoMoreConstants)?;
+                Ok(spec.add_const(word, c))
+            })
+            .collect::<Result<_, PermError>>()?;
+
+        if full {
+            for word in new_words.iter_mut() {
+                *word = spec.s_box(word);
+            }
+        } else {
+            new_words[0] = spec.s_box(&new_words[0]);
+        }
+
+        Ok(spec.mds_multiply(&self.matrix, new_words))
     }
-}
 
-// Apply full round
-impl Permutation {
-    fn apply_full_round(
+    /// The optimized partial rounds: one dense matrix application for the
+    /// first round, folded to account for every later round, followed by
+    /// one sparse matrix per remaining round, with only a single constant
+    /// added per round after the first.
+    fn apply_partial_rounds_optimized<S: Specification>(
         &self,
-        constants: &mut RoundConstantsIterator,
-        words: Vec<Scalar>,
-    ) -> Result<Vec<Scalar>, PermError> {
-        // Add round keys to each word
-        let new_words = self.add_round_key(constants, words)?;
+        spec: &mut S,
+        words: Vec<S::Field>,
+    ) -> Result<Vec<S::Field>, PermError> {
+        let mut constants = self.optimized_partial_constants.chunks(self.t);
+        let c0 = constants.next().ok_or(PermError::NoMoreConstants)?;
 
-        // Then apply quintic s-box
-        let quintic_words: Result<Vec<Scalar>, PermError> = new_words
+        let mut new_words: Vec<S::Field> = words
             .iter()
-            .map(|word| Ok(Permutation::quintic_s_box(word)))
+            .zip(c0.iter())
+            .map(|(w, c)| spec.add_const(w, c))
             .collect();
+        new_words[0] = spec.s_box(&new_words[0]);
+        new_words = spec.mds_multiply(&self.pre_sparse_matrix, new_words);
 
-        // Multiply this result by the MDS matrix
-        Ok(self.matrix.mul_vector(&quintic_words?))
+        for (sparse, c) in self.sparse_matrices.iter().zip(constants) {
+            new_words[0] = spec.add_const(&new_words[0], &c[0]);
+            new_words[0] = spec.s_box(&new_words[0]);
+            new_words = spec.sparse_multiply(sparse, new_words);
+        }
+
+        Ok(new_words)
     }
+}
 
-    fn constrain_apply_full_round(
-        &self,
-        constants: &mut RoundConstantsIterator,
-        words: Vec<LinearCombination>,
-        cs: &mut dyn ConstraintSystem,
-    ) -> Result<Vec<LinearCombination>, PermError> {
-        // Add round keys to each word
-        let new_words = self.constrain_add_round_key(constants, words)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let quintic_words: Result<Vec<LinearCombination>, PermError> = new_words
-            .iter()
-            .map(|word| Ok(Permutation::constrain_quintic_s_box(word.clone(), cs)))
-            .collect();
+    #[test]
+    fn optimized_partial_rounds_match_reference() {
+        let mut perm = Permutation::default();
+        let width = perm.width();
+        perm.inputs((0..width).map(|i| Scalar::from(i as u64 + 1)).collect())
+            .unwrap();
 
-        // Multiply this result by the MDS matrix
-        Ok(self.matrix.constrain_mul_vector(quintic_words?))
+        assert_eq!(perm.result().unwrap(), perm.result_reference().unwrap());
     }
-}
 
-// Add round key
-impl Permutation {
-    fn add_round_key(
-        &self,
-        constants: &mut RoundConstantsIterator,
-        words: Vec<Scalar>,
-    ) -> Result<Vec<Scalar>, PermError> {
-        words
-            .iter()
-            .map(|word| {
-                let c = constants.next().ok_or(PermError::NoMoreConstants)?;
-                Ok(word + c)
-            })
-            .collect()
+    #[test]
+    fn with_params_seed_is_reproducible() {
+        let (t, full_rounds, partial_rounds) = (3, 2, 3);
+        let inputs: Vec<Scalar> = (0..t).map(|i| Scalar::from(i as u64 + 1)).collect();
+
+        let run = || {
+            let mut perm = Permutation::with_params(
+                t,
+                full_rounds,
+                partial_rounds,
+                Alpha::Exponent(5),
+                Some(b"hades252-test-seed".as_slice()),
+                None,
+                None,
+            )
+            .unwrap();
+            perm.inputs(inputs.clone()).unwrap();
+            perm.result().unwrap()
+        };
+
+        assert_eq!(run(), run());
     }
 
-    fn constrain_add_round_key(
-        &self,
-        constants: &mut RoundConstantsIterator,
-        words: Vec<LinearCombination>,
-    ) -> Result<Vec<LinearCombination>, PermError> {
-        words
-            .iter()
-            .map(|word| {
-                // First get the constant needed and convert it to a linear combination
-                let c = constants.next().ok_or(PermError::NoMoreConstants)?;
-                let c_lc = LinearCombination::from(c.clone());
-                // Return words + constants
-                Ok(word.clone() + c_lc)
-            })
-            .collect()
+    /// `with_params` with an explicit matrix and constant schedule exercises
+    /// the same `factorize_alpha`/`fold_partial_round_constants` path as
+    /// the generated-parameter case, with the canonical matrix passed in
+    /// through `MDSMatrix::from_matrix` and a hand-written constant
+    /// schedule — so its result should still match the unoptimized
+    /// reference evaluation.
+    #[test]
+    fn with_params_explicit_matrix_and_constants_match_reference() {
+        let (t, full_rounds, partial_rounds) = (3, 2, 3);
+
+        let matrix = MDSMatrix::from_matrix(MDSMatrix::generate(t).rows().to_vec(), t).unwrap();
+        let constants: Vec<Scalar> = (0..(full_rounds + partial_rounds) * t)
+            .map(|i| Scalar::from(i as u64 + 1))
+            .collect();
+
+        let mut perm = Permutation::with_params(
+            t,
+            full_rounds,
+            partial_rounds,
+            Alpha::Exponent(5),
+            None,
+            Some(constants),
+            Some(matrix),
+        )
+        .unwrap();
+        perm.inputs((0..t).map(|i| Scalar::from(i as u64 + 1)).collect())
+            .unwrap();
+
+        assert_eq!(perm.result().unwrap(), perm.result_reference().unwrap());
     }
-}
 
-impl Permutation {
-    fn quintic_s_box(scalar: &Scalar) -> Scalar {
-        scalar * scalar * scalar * scalar * scalar
+    /// A `Specification` matching the circuit's behavior *before* `Elt`
+    /// deferral: every `add`/`add_const` immediately forces an allocation
+    /// (via a multiplication by the constant `1`) instead of staying a free
+    /// linear combination. Kept only to measure what the eager approach
+    /// would have cost; see `elt_deferral_reduces_constraint_count`.
+    struct EagerCircuitSpec<'a> {
+        cs: &'a mut dyn ConstraintSystem,
+        alpha: Alpha,
     }
-    fn constrain_quintic_s_box(
-        lc: LinearCombination,
-        cs: &mut dyn ConstraintSystem,
-    ) -> LinearCombination {
-        let (lc, _, square) = cs.multiply(lc.clone(), lc);
-        let (_, _, quartic) = cs.multiply(square.into(), square.into());
-        let (_, _, quintic) = cs.multiply(quartic.into(), lc.into());
 
-        quintic.into()
+    impl<'a> Specification for EagerCircuitSpec<'a> {
+        type Field = LinearCombination;
+
+        fn zero(&mut self) -> LinearCombination {
+            LinearCombination::default()
+        }
+        fn add(&mut self, lhs: &LinearCombination, rhs: &LinearCombination) -> LinearCombination {
+            let (_, _, out) = self
+                .cs
+                .multiply(lhs.clone() + rhs.clone(), LinearCombination::from(Scalar::one()));
+            out.into()
+        }
+        fn mul(&mut self, lhs: &LinearCombination, rhs: &LinearCombination) -> LinearCombination {
+            let (_, _, out) = self.cs.multiply(lhs.clone(), rhs.clone());
+            out.into()
+        }
+        fn add_const(&mut self, lhs: &LinearCombination, rhs: &Scalar) -> LinearCombination {
+            let (_, _, out) = self.cs.multiply(
+                lhs.clone() + LinearCombination::from(*rhs),
+                LinearCombination::from(Scalar::one()),
+            );
+            out.into()
+        }
+        fn s_box(&mut self, x: &LinearCombination) -> LinearCombination {
+            match self.alpha {
+                Alpha::Exponent(e) => {
+                    let mut base = x.clone();
+                    let mut result: Option<LinearCombination> = None;
+                    let mut exponent = e;
+                    while exponent > 0 {
+                        if exponent & 1 == 1 {
+                            result = Some(match result {
+                                Some(acc) => self.mul(&acc, &base),
+                                None => base.clone(),
+                            });
+                        }
+                        exponent >>= 1;
+                        if exponent > 0 {
+                            base = self.mul(&base, &base);
+                        }
+                    }
+                    result.unwrap_or_else(|| LinearCombination::from(Scalar::one()))
+                }
+                Alpha::Inverse => unimplemented!("not exercised by this test"),
+            }
+        }
+        fn mds_multiply(
+            &mut self,
+            matrix: &MDSMatrix,
+            words: Vec<LinearCombination>,
+        ) -> Vec<LinearCombination> {
+            matrix
+                .constrain_mul_vector(words)
+                .into_iter()
+                .map(|lc| {
+                    let (_, _, out) = self.cs.multiply(lc, LinearCombination::from(Scalar::one()));
+                    out.into()
+                })
+                .collect()
+        }
+        fn sparse_multiply(
+            &mut self,
+            matrix: &SparseMDSMatrix,
+            words: Vec<LinearCombination>,
+        ) -> Vec<LinearCombination> {
+            matrix
+                .constrain_mul_vector(words)
+                .into_iter()
+                .map(|lc| {
+                    let (_, _, out) = self.cs.multiply(lc, LinearCombination::from(Scalar::one()));
+                    out.into()
+                })
+                .collect()
+        }
+    }
+
+    /// Deferring additions as un-allocated `Elt::Lc` (this module's
+    /// `CircuitSpec`) should emit strictly fewer multiplier constraints
+    /// than eagerly allocating on every add (`EagerCircuitSpec`), while
+    /// computing the same permutation.
+    #[test]
+    fn elt_deferral_reduces_constraint_count() {
+        use bulletproofs::r1cs::Prover;
+        use bulletproofs::PedersenGens;
+        use merlin::Transcript;
+
+        let perm = Permutation::default();
+        let width = perm.width();
+        let inputs: Vec<Scalar> = (0..width).map(|i| Scalar::from(i as u64 + 1)).collect();
+
+        let pc_gens = PedersenGens::default();
+
+        let optimized_multipliers = {
+            let mut transcript = Transcript::new(b"elt-deferral-test");
+            let mut prover = Prover::new(&pc_gens, &mut transcript);
+            let vars: Vec<Variable> = inputs
+                .iter()
+                .map(|s| prover.allocate(Some(*s)).unwrap())
+                .collect();
+            let words = vars.into_iter().map(Elt::from).collect();
+            perm.permute(
+                &mut CircuitSpec {
+                    cs: &mut prover,
+                    alpha: perm.alpha,
+                },
+                words,
+            )
+            .unwrap();
+            prover.metrics().multipliers
+        };
+
+        let eager_multipliers = {
+            let mut transcript = Transcript::new(b"elt-deferral-test-eager");
+            let mut prover = Prover::new(&pc_gens, &mut transcript);
+            let vars: Vec<Variable> = inputs
+                .iter()
+                .map(|s| prover.allocate(Some(*s)).unwrap())
+                .collect();
+            let words = vars.into_iter().map(LinearCombination::from).collect();
+            perm.permute(
+                &mut EagerCircuitSpec {
+                    cs: &mut prover,
+                    alpha: perm.alpha,
+                },
+                words,
+            )
+            .unwrap();
+            prover.metrics().multipliers
+        };
+
+        assert!(optimized_multipliers < eager_multipliers);
     }
 }
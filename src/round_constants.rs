@@ -0,0 +1,127 @@
+use crate::errors::PermError;
+use crate::mds_matrix::{invert, mat_vec_mul};
+use curve25519_dalek::scalar::Scalar;
+use sha2::{Digest, Sha512};
+use std::slice::Iter;
+
+/// Domain-separation tag used by [`RoundConstants::generate`], which is
+/// just [`RoundConstants::generate_with_seed`] with this tag and an empty
+/// seed.
+const DEFAULT_DOMAIN_TAG: &[u8] = b"hades252-round-constant";
+
+/// The flat schedule of round constants added into the permutation state,
+/// one scalar per word per round.
+#[derive(Debug, Clone)]
+pub struct RoundConstants(Vec<Scalar>);
+
+/// An iterator over a [`RoundConstants`] schedule, consumed one constant at
+/// a time as rounds are applied.
+pub struct RoundConstantsIterator<'a> {
+    inner: Iter<'a, Scalar>,
+}
+
+impl RoundConstants {
+    /// Derive `(full_rounds + partial_rounds) * t` round constants from a
+    /// fixed domain-separation tag and no seed.
+    pub fn generate(full_rounds: usize, partial_rounds: usize, t: usize) -> Self {
+        Self::generate_with_seed(full_rounds, partial_rounds, t, DEFAULT_DOMAIN_TAG, &[])
+    }
+
+    /// Derive round constants deterministically from `hash(tag || seed ||
+    /// index)`, for `index` in `0..(full_rounds + partial_rounds) * t`.
+    ///
+    /// Two deployments that use different `tag`/`seed` values get
+    /// distinct-but-reproducible constants; the same `tag`/`seed` always
+    /// reproduces the same schedule, which lets independent
+    /// implementations agree on a parameter set, or a test pin a fixed
+    /// reference vector.
+    pub fn generate_with_seed(
+        full_rounds: usize,
+        partial_rounds: usize,
+        t: usize,
+        tag: &[u8],
+        seed: &[u8],
+    ) -> Self {
+        let amount = (full_rounds + partial_rounds) * t;
+        let constants = (0..amount)
+            .map(|i| {
+                let mut hasher = Sha512::new();
+                hasher.update(tag);
+                hasher.update(seed);
+                hasher.update(&(i as u64).to_le_bytes());
+                Scalar::hash_from_bytes::<Sha512>(&hasher.finalize())
+            })
+            .collect();
+
+        RoundConstants(constants)
+    }
+
+    /// Use an externally supplied constant schedule verbatim — e.g. an
+    /// audited parameter set, or one needed to match a reference test
+    /// vector from another implementation — after checking it has the
+    /// length the permutation expects.
+    pub fn from_constants(
+        constants: Vec<Scalar>,
+        full_rounds: usize,
+        partial_rounds: usize,
+        t: usize,
+    ) -> Result<Self, PermError> {
+        if constants.len() != (full_rounds + partial_rounds) * t {
+            return Err(PermError::InvalidParameterLength);
+        }
+
+        Ok(RoundConstants(constants))
+    }
+
+    pub fn iter(&self) -> RoundConstantsIterator {
+        RoundConstantsIterator {
+            inner: self.0.iter(),
+        }
+    }
+
+    /// Return the constants as a flat slice, e.g. to fold the partial-round
+    /// block via [`fold_partial_round_constants`].
+    pub fn as_slice(&self) -> &[Scalar] {
+        &self.0
+    }
+}
+
+/// Fold the constants added to words `1..t` of each partial round backward
+/// through the (dense) MDS layer, so that the optimized partial round
+/// introduced in [`crate::mds_matrix::MDSMatrix::factorize_alpha`] only
+/// needs to add a single scalar, to word `0`, per round.
+///
+/// Word `0` alone passes through the S-box each partial round, so its
+/// constant cannot be folded away; every other word's constant is instead
+/// pushed, through the inverse MDS matrix, into the constant vector of the
+/// *previous* round, where it is absorbed before that round's own MDS
+/// multiplication takes place.
+pub fn fold_partial_round_constants(
+    partial_constants: &[Scalar],
+    matrix: &[Vec<Scalar>],
+    partial_rounds: usize,
+    t: usize,
+) -> Vec<Scalar> {
+    let matrix_inv = invert(matrix);
+    let mut rounds: Vec<Vec<Scalar>> = partial_constants.chunks(t).map(|c| c.to_vec()).collect();
+
+    for r in (1..partial_rounds).rev() {
+        let carry = rounds[r].clone();
+        let pushed = mat_vec_mul(&matrix_inv, &carry);
+        for i in 0..t {
+            rounds[r - 1][i] += pushed[i];
+        }
+        rounds[r] = vec![Scalar::zero(); t];
+        rounds[r][0] = carry[0];
+    }
+
+    rounds.into_iter().flatten().collect()
+}
+
+impl<'a> Iterator for RoundConstantsIterator<'a> {
+    type Item = &'a Scalar;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
@@ -0,0 +1,333 @@
+//! Field-arithmetic abstraction for a single Hades252 round, so the same
+//! round logic can run natively over `Scalar` or symbolically inside a
+//! bulletproofs r1cs circuit over `LinearCombination`, without the two
+//! targets being hand-duplicated into parallel `apply_*`/`constrain_apply_*`
+//! methods.
+//!
+//! This follows the `Specification`-trait pattern from the OpenZL ECLAIR
+//! tutorial: one trait describing the handful of operations a round needs,
+//! one implementation per backend.
+
+use crate::elt::Elt;
+use crate::errors::PermError;
+use crate::mds_matrix::{MDSMatrix, SparseMDSMatrix};
+use bulletproofs::r1cs::{ConstraintSystem, LinearCombination};
+use curve25519_dalek::scalar::Scalar;
+
+/// The S-box exponent a [`crate::permutation::Permutation`] raises each
+/// word to. `Exponent(alpha)` covers the small positive odd exponents
+/// (3, 5, ...) Poseidon is usually instantiated with; `Inverse` covers the
+/// `x^(p-2)` S-box used when the field characteristic makes every small
+/// odd exponent unsuitable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alpha {
+    Exponent(u64),
+    Inverse,
+}
+
+impl Alpha {
+    /// `x -> x^alpha` is a permutation of the field iff `alpha` is coprime
+    /// to `p - 1`; this checks that for the `Exponent` variant (`Inverse`
+    /// is always valid: `gcd(p - 2, p - 1) = gcd(-1, p - 1) = 1`).
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Alpha::Exponent(e) => {
+                *e > 0 && gcd(*e, scalar_mod_u64(&(Scalar::zero() - Scalar::one()), *e)) == 1
+            }
+            Alpha::Inverse => true,
+        }
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Reduce a `Scalar`, read as the integer it represents, modulo a small
+/// `u64` modulus, via Horner's method over its big-endian byte string.
+fn scalar_mod_u64(value: &Scalar, modulus: u64) -> u64 {
+    value
+        .as_bytes()
+        .iter()
+        .rev()
+        .fold(0u64, |acc, &byte| (acc * 256 + byte as u64) % modulus)
+}
+
+/// The field operations a Hades252 round needs, parameterized over the
+/// backend's representation of a permutation word (`Field`).
+pub trait Specification {
+    /// The representation of a single permutation word under this backend.
+    type Field: Clone;
+
+    fn zero(&mut self) -> Self::Field;
+    fn add(&mut self, lhs: &Self::Field, rhs: &Self::Field) -> Self::Field;
+    fn mul(&mut self, lhs: &Self::Field, rhs: &Self::Field) -> Self::Field;
+    fn add_const(&mut self, lhs: &Self::Field, rhs: &Scalar) -> Self::Field;
+
+    /// Raise `x` to the permutation's S-box power.
+    fn s_box(&mut self, x: &Self::Field) -> Self::Field;
+
+    /// Multiply a full state vector by a dense MDS matrix.
+    fn mds_multiply(&mut self, matrix: &MDSMatrix, words: Vec<Self::Field>) -> Vec<Self::Field>;
+    /// Multiply a full state vector by one of the optimized partial-round
+    /// sparse matrices.
+    fn sparse_multiply(
+        &mut self,
+        matrix: &SparseMDSMatrix,
+        words: Vec<Self::Field>,
+    ) -> Vec<Self::Field>;
+}
+
+/// Native evaluation of the permutation over `curve25519_dalek::Scalar`.
+pub struct NativeSpec {
+    pub alpha: Alpha,
+}
+
+impl Specification for NativeSpec {
+    type Field = Scalar;
+
+    fn zero(&mut self) -> Scalar {
+        Scalar::zero()
+    }
+    fn add(&mut self, lhs: &Scalar, rhs: &Scalar) -> Scalar {
+        lhs + rhs
+    }
+    fn mul(&mut self, lhs: &Scalar, rhs: &Scalar) -> Scalar {
+        lhs * rhs
+    }
+    fn add_const(&mut self, lhs: &Scalar, rhs: &Scalar) -> Scalar {
+        lhs + rhs
+    }
+    fn s_box(&mut self, x: &Scalar) -> Scalar {
+        match self.alpha {
+            Alpha::Exponent(e) => pow_via_addition_chain(x, e),
+            // x = 0 has no inverse; map it to 0 rather than to p-2's
+            // native undefined behavior.
+            Alpha::Inverse if x == &Scalar::zero() => Scalar::zero(),
+            Alpha::Inverse => x.invert(),
+        }
+    }
+    fn mds_multiply(&mut self, matrix: &MDSMatrix, words: Vec<Scalar>) -> Vec<Scalar> {
+        matrix.mul_vector(&words)
+    }
+    fn sparse_multiply(&mut self, matrix: &SparseMDSMatrix, words: Vec<Scalar>) -> Vec<Scalar> {
+        matrix.mul_vector(&words)
+    }
+}
+
+/// `x^e` via square-and-multiply; for the exponents Poseidon is usually
+/// instantiated with (3, 5) this is exactly the textbook short addition
+/// chain (one squaring plus one multiply for `e = 3`, two squarings plus
+/// one multiply for `e = 5`).
+fn pow_via_addition_chain(x: &Scalar, e: u64) -> Scalar {
+    let mut base = *x;
+    let mut result = Scalar::one();
+    let mut exponent = e;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result *= base;
+        }
+        exponent >>= 1;
+        if exponent > 0 {
+            base *= base;
+        }
+    }
+    result
+}
+
+/// In-circuit evaluation of the permutation, compiling each round into
+/// bulletproofs r1cs constraints over [`Elt`].
+///
+/// Additions (`add`, `add_const`) and the dense/sparse MDS layers only ever
+/// combine linear combinations and never touch `cs`, so they stay `Elt::Lc`
+/// and cost nothing; only the S-box allocates multipliers — through `mul`
+/// for `Alpha::Exponent`, through `inverse_circuit`'s own
+/// `allocate_multiplier` calls for `Alpha::Inverse` — and so is the only
+/// source of constraints.
+pub struct CircuitSpec<'a> {
+    pub cs: &'a mut dyn ConstraintSystem,
+    pub alpha: Alpha,
+}
+
+/// `Some` of every `Elt`'s witness if all of them carry one, so a dense or
+/// sparse matrix multiply can be mirrored natively to keep the witness
+/// flowing; `None` as soon as a single input's witness is missing.
+fn all_witnesses(words: &[Elt]) -> Option<Vec<Scalar>> {
+    words.iter().map(Elt::witness).collect()
+}
+
+/// Pair each output linear combination with its corresponding native
+/// witness, when one was computed.
+fn zip_lc_witness(lcs: Vec<LinearCombination>, witnesses: Option<Vec<Scalar>>) -> Vec<Elt> {
+    match witnesses {
+        Some(witnesses) => lcs
+            .into_iter()
+            .zip(witnesses)
+            .map(|(lc, w)| Elt::Lc(lc, Some(w)))
+            .collect(),
+        None => lcs.into_iter().map(|lc| Elt::Lc(lc, None)).collect(),
+    }
+}
+
+impl<'a> Specification for CircuitSpec<'a> {
+    type Field = Elt;
+
+    fn zero(&mut self) -> Elt {
+        Elt::Lc(LinearCombination::default(), Some(Scalar::zero()))
+    }
+    fn add(&mut self, lhs: &Elt, rhs: &Elt) -> Elt {
+        let witness = lhs.witness().zip(rhs.witness()).map(|(l, r)| l + r);
+        Elt::Lc(lhs.lc() + rhs.lc(), witness)
+    }
+    fn mul(&mut self, lhs: &Elt, rhs: &Elt) -> Elt {
+        let witness = lhs.witness().zip(rhs.witness()).map(|(l, r)| l * r);
+        let (_, _, out) = self.cs.multiply(lhs.lc(), rhs.lc());
+        Elt::Allocated(out, witness)
+    }
+    fn add_const(&mut self, lhs: &Elt, rhs: &Scalar) -> Elt {
+        let witness = lhs.witness().map(|l| l + rhs);
+        Elt::Lc(lhs.lc() + LinearCombination::from(*rhs), witness)
+    }
+    fn s_box(&mut self, x: &Elt) -> Elt {
+        match self.alpha {
+            Alpha::Exponent(e) => self.pow_circuit(x, e),
+            Alpha::Inverse => self.inverse_circuit(x),
+        }
+    }
+    fn mds_multiply(&mut self, matrix: &MDSMatrix, words: Vec<Elt>) -> Vec<Elt> {
+        let witnesses = all_witnesses(&words).map(|w| matrix.mul_vector(&w));
+        let lcs = words.iter().map(Elt::lc).collect();
+        zip_lc_witness(matrix.constrain_mul_vector(lcs), witnesses)
+    }
+    fn sparse_multiply(&mut self, matrix: &SparseMDSMatrix, words: Vec<Elt>) -> Vec<Elt> {
+        let witnesses = all_witnesses(&words).map(|w| matrix.mul_vector(&w));
+        let lcs = words.iter().map(Elt::lc).collect();
+        zip_lc_witness(matrix.constrain_mul_vector(lcs), witnesses)
+    }
+}
+
+impl<'a> CircuitSpec<'a> {
+    /// Mirrors `pow_via_addition_chain`, emitting one `cs.multiply` per
+    /// step of the square-and-multiply chain instead of a native `*`.
+    fn pow_circuit(&mut self, x: &Elt, e: u64) -> Elt {
+        let mut base = x.clone();
+        let mut result: Option<Elt> = None;
+        let mut exponent = e;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = Some(match result {
+                    Some(acc) => self.mul(&acc, &base),
+                    None => base.clone(),
+                });
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = self.mul(&base, &base);
+            }
+        }
+        result.unwrap_or_else(|| Elt::Lc(LinearCombination::from(Scalar::one()), Some(Scalar::one())))
+    }
+
+    /// `y = x^(-1)` guarded so that `x = 0` constrains to `y = 0` instead
+    /// of being unsatisfiable: alongside `y`, allocate a bit `is_zero` and
+    /// constrain `x * y = 1 - is_zero`, `is_zero * x = 0` and
+    /// `is_zero * y = 0`. An honest prover sets `is_zero = 1, y = 0` when
+    /// `x = 0` and `is_zero = 0, y = x^(-1)` otherwise; the first two
+    /// equations alone only pin `is_zero` as a 0/1 flag for "x is zero"
+    /// and leave `y` free whenever `x = 0`, so the third equation is what
+    /// actually forces `y = 0` in that case.
+    ///
+    /// There's no way to recover `x`'s value from inside the circuit: a
+    /// `Prover` only exposes that for debugging, and a `Verifier` never has
+    /// it at all. So, like `constrain_sponge`/`constrain_encrypt`, this
+    /// relies on `x` already carrying its own witness (see [`Elt`]) rather
+    /// than reading one back out of `self.cs`; without one (e.g. when
+    /// called through a `Verifier`), every witness below is simply `None`,
+    /// which `allocate_multiplier` treats the same way the rest of this
+    /// circuit's allocations do when run without a prover.
+    fn inverse_circuit(&mut self, x: &Elt) -> Elt {
+        let x_lc = x.lc();
+        let witnesses = x.witness().map(|x_val| {
+            let is_zero = x_val == Scalar::zero();
+            let y_val = if is_zero { Scalar::zero() } else { x_val.invert() };
+            let is_zero_val = if is_zero { Scalar::one() } else { Scalar::zero() };
+            (x_val, y_val, is_zero_val)
+        });
+
+        let (x_var, y_var, xy_var) = self
+            .cs
+            .allocate_multiplier(witnesses.map(|(x_val, y_val, _)| (x_val, y_val)))
+            .expect("failed to allocate s-box inverse witness");
+        self.cs.constrain(x_lc.clone() - x_var);
+
+        let (is_zero_var, x_var_2, zero_x_var) = self
+            .cs
+            .allocate_multiplier(witnesses.map(|(x_val, _, is_zero_val)| (is_zero_val, x_val)))
+            .expect("failed to allocate s-box is_zero witness");
+        self.cs.constrain(x_lc - x_var_2);
+        self.cs.constrain(LinearCombination::from(zero_x_var));
+        self.cs.constrain(
+            LinearCombination::from(xy_var)
+                - (LinearCombination::from(Scalar::one()) - LinearCombination::from(is_zero_var)),
+        );
+
+        let (is_zero_var_2, y_var_2, zero_y_var) = self
+            .cs
+            .allocate_multiplier(witnesses.map(|(_, y_val, is_zero_val)| (is_zero_val, y_val)))
+            .expect("failed to allocate s-box y-zeroing witness");
+        self.cs
+            .constrain(LinearCombination::from(is_zero_var) - is_zero_var_2);
+        self.cs.constrain(LinearCombination::from(y_var) - y_var_2);
+        self.cs.constrain(LinearCombination::from(zero_y_var));
+
+        Elt::Allocated(y_var, witnesses.map(|(_, y_val, _)| y_val))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bulletproofs::r1cs::Prover;
+    use bulletproofs::PedersenGens;
+    use merlin::Transcript;
+
+    /// Run `CircuitSpec::s_box` under `Alpha::Inverse` on a single witness
+    /// `x` and return the resulting `y`'s value, so it can be compared
+    /// against `NativeSpec`.
+    fn circuit_inverse(x_val: Scalar) -> Scalar {
+        let pc_gens = PedersenGens::default();
+        let mut transcript = Transcript::new(b"inverse-circuit-test");
+        let mut prover = Prover::new(&pc_gens, &mut transcript);
+        let x_var = prover.allocate(Some(x_val)).unwrap();
+
+        let mut spec = CircuitSpec {
+            cs: &mut prover,
+            alpha: Alpha::Inverse,
+        };
+        let y = spec.s_box(&Elt::allocated(x_var, x_val));
+        y.witness().expect("inverse_circuit drops the witness it was given")
+    }
+
+    #[test]
+    fn inverse_circuit_matches_native_for_nonzero_x() {
+        let x = Scalar::from(7u64);
+        let expected = NativeSpec { alpha: Alpha::Inverse }.s_box(&x);
+        assert_eq!(circuit_inverse(x), expected);
+    }
+
+    /// For `x = 0` the honest witness is `y = 0`, matching `NativeSpec`.
+    /// This checks only that witness value; that the `is_zero * y = 0`
+    /// constraint forces `y = 0` against a cheating prover is not
+    /// exercised here.
+    #[test]
+    fn inverse_circuit_matches_native_for_zero_x() {
+        let x = Scalar::zero();
+        let expected = NativeSpec { alpha: Alpha::Inverse }.s_box(&x);
+        assert_eq!(expected, Scalar::zero());
+        assert_eq!(circuit_inverse(x), Scalar::zero());
+    }
+}